@@ -1,8 +1,8 @@
 use url::Url;
 use regex::Regex;
 use serde::Deserialize;
-use reqwest::{Result, StatusCode};
-use reqwest::blocking::{Client, Response};
+use reqwest::{Client, Response, Result, StatusCode};
+use async_trait::async_trait;
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum GithubIssueType {
@@ -18,58 +18,212 @@ fn issue_type_from_string(str: &str) -> GithubIssueType {
     }
 }
 
+#[derive(Debug, Eq, PartialEq)]
+pub enum GitlabIssueType {
+    Issue,
+    MergeRequest,
+}
+
+fn gitlab_issue_type_from_string(str: &str) -> GitlabIssueType {
+    if str == "issues" {
+        GitlabIssueType::Issue
+    } else {
+        GitlabIssueType::MergeRequest
+    }
+}
+
+/// Self-hosted GitLab instances are indistinguishable from arbitrary hosts by URL shape alone, so they must be listed in `extra_hosts`.
+fn is_gitlab_host(host: &str, extra_hosts: &[String]) -> bool {
+    host.eq_ignore_ascii_case("gitlab.com") || extra_hosts.iter().any(|extra| host.eq_ignore_ascii_case(extra))
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum Issue<'u> {
     Github(&'u str, &'u str, &'u str, GithubIssueType, &'u Url),
+    Gitlab(&'u str, &'u str, &'u str, GitlabIssueType, &'u Url),
     Link(&'u Url),
 }
 
-pub fn issue_from_url(url: &Url) -> Issue {
+pub fn issue_from_url<'u>(url: &'u Url, gitlab_hosts: &[String]) -> Issue<'u> {
     let github_regex = Regex::new(r"(?i)github.com/(.+?)/(.+?)/(issues|pull)/(\d+)$").unwrap();
-    return if let Some(capture) = github_regex.captures(url.as_str()) {
+    if let Some(capture) = github_regex.captures(url.as_str()) {
         let issue_type_string = capture.get(3).unwrap().as_str();
         let issue_type = issue_type_from_string(issue_type_string);
 
-        Issue::Github(
+        return Issue::Github(
             capture.get(1).unwrap().as_str(),
             capture.get(2).unwrap().as_str(),
             capture.get(4).unwrap().as_str(),
             issue_type,
             url,
-        )
-    } else {
-        Issue::Link(url)
-    };
+        );
+    }
+
+    // Project paths can nest arbitrarily; the capture is lazy so an optional `-/` before the tracker segment stays out of the path.
+    let gitlab_regex = Regex::new(r"(?i)^https?://([^/]+)/(.+?)/(?:-/)?(issues|merge_requests)/(\d+)$").unwrap();
+    if let Some(capture) = gitlab_regex.captures(url.as_str()) {
+        let host = capture.get(1).unwrap().as_str();
+        if is_gitlab_host(host, gitlab_hosts) {
+            let issue_type_string = capture.get(3).unwrap().as_str();
+            let issue_type = gitlab_issue_type_from_string(issue_type_string);
+
+            return Issue::Gitlab(
+                host,
+                capture.get(2).unwrap().as_str(),
+                capture.get(4).unwrap().as_str(),
+                issue_type,
+                url,
+            );
+        }
+    }
+
+    Issue::Link(url)
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
 pub enum ValidationResult {
     NoLongerValid,
     StillValid,
+    /// The validator couldn't determine a definitive state (rate limit, network error, etc).
+    Unknown,
+}
+
+#[derive(Deserialize, Debug, Eq, PartialEq)]
+struct Label {
+    name: String,
+}
+
+#[derive(Deserialize, Debug, Eq, PartialEq)]
+struct Milestone {
+    title: String,
 }
 
 #[derive(Deserialize, Debug, Eq, PartialEq)]
 struct IssueResult {
-    state: String
+    state: String,
+    #[serde(default)]
+    merged_at: Option<String>,
+    #[serde(default)]
+    labels: Vec<Label>,
+    #[serde(default)]
+    milestone: Option<Milestone>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub enum ValidationCondition {
+    WhileOpen,
+    UntilMerged,
+    WhileLabeled(String),
+}
+
+impl Default for ValidationCondition {
+    fn default() -> Self {
+        ValidationCondition::WhileOpen
+    }
+}
+
+impl ValidationCondition {
+    pub fn parse(condition: &str) -> ValidationCondition {
+        if condition == "until-merged" {
+            ValidationCondition::UntilMerged
+        } else if let Some(label) = condition.strip_prefix("while-labeled:") {
+            ValidationCondition::WhileLabeled(label.to_string())
+        } else {
+            ValidationCondition::WhileOpen
+        }
+    }
+}
+
+fn github_condition_satisfied(issue: &IssueResult, condition: &ValidationCondition) -> bool {
+    match condition {
+        ValidationCondition::WhileOpen => issue.state.as_str() == "open",
+        ValidationCondition::UntilMerged => issue.merged_at.is_none(),
+        ValidationCondition::WhileLabeled(label) => issue.labels.iter().any(|l| &l.name == label),
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ValidationContext {
+    pub github_token: Option<String>,
+    pub gitlab_token: Option<String>,
+}
+
+/// Independent of any section's `ValidationCondition`, so one fetch can be resolved against several conditions without re-hitting the network.
+#[derive(Debug)]
+pub enum IssueState {
+    Github(IssueResult),
+    Resolved(ValidationResult),
 }
 
+impl IssueState {
+    pub fn resolve(&self, condition: &ValidationCondition) -> ValidationResult {
+        match self {
+            IssueState::Github(issue) => {
+                if github_condition_satisfied(issue, condition) {
+                    ValidationResult::StillValid
+                } else {
+                    ValidationResult::NoLongerValid
+                }
+            },
+            IssueState::Resolved(result) => *result,
+        }
+    }
+}
+
+#[async_trait]
 pub trait IssueValidator {
-    fn validate(&self, issue: &Issue) -> ValidationResult;
+    async fn validate(&self, issue: &Issue, context: &ValidationContext) -> IssueState;
+}
+
+pub struct DefaultIssueValidator {
+    client: Client,
+    github_token: Option<String>,
+    gitlab_token: Option<String>,
+}
+
+impl Default for DefaultIssueValidator {
+    fn default() -> Self {
+        let github_token = std::env::var("GITHUB_TOKEN")
+            .or_else(|_| std::env::var("GH_TOKEN"))
+            .ok();
+        let gitlab_token = std::env::var("GITLAB_TOKEN").ok();
+        DefaultIssueValidator { client: Client::new(), github_token, gitlab_token }
+    }
 }
 
-pub struct DefaultIssueValidator;
+impl DefaultIssueValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
 
+#[async_trait]
 impl IssueValidator for DefaultIssueValidator {
-    fn validate(&self, issue: &Issue) -> ValidationResult {
+    async fn validate(&self, issue: &Issue, context: &ValidationContext) -> IssueState {
         match issue {
-            Issue::Github(owner, repo, number, issue_type, _url) => self.github_validation_result(owner, repo, number, issue_type),
-            Issue::Link(url) => self.arbitrary_url_validation_result(url)
+            Issue::Github(owner, repo, number, issue_type, _url) => self.github_validation_result(owner, repo, number, issue_type, context).await,
+            Issue::Gitlab(host, project_path, number, issue_type, _url) => IssueState::Resolved(self.gitlab_validation_result(host, project_path, number, issue_type, context).await),
+            Issue::Link(url) => IssueState::Resolved(self.arbitrary_url_validation_result(url).await)
         }
     }
 }
 
+/// A rate-limited response is an infrastructure failure, not evidence the issue is closed.
+fn is_rate_limited(response: &Response) -> bool {
+    let status = response.status();
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return false;
+    }
+    let headers = response.headers();
+    let remaining_exhausted = headers.get("x-ratelimit-remaining")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value == "0")
+        .unwrap_or(false);
+    remaining_exhausted || headers.contains_key("retry-after")
+}
+
 impl DefaultIssueValidator {
-    fn github_validation_result(&self, owner: &str, repo: &str, number: &str, issue_type: &GithubIssueType) -> ValidationResult {
+    async fn github_validation_result(&self, owner: &str, repo: &str, number: &str, issue_type: &GithubIssueType, context: &ValidationContext) -> IssueState {
         let issue_kind = match issue_type {
             GithubIssueType::Issue => "issues",
             GithubIssueType::PullRequest => "pulls"
@@ -82,30 +236,82 @@ impl DefaultIssueValidator {
             issue_kind = issue_kind,
             number = number
         );
-        let client = Client::new();
-        let request = client.get(&request_url)
+        let mut request = self.client.get(&request_url)
             .header("User-Agent", "younata/mdbook-section-validator");
-        let send_result: Result<Response> = request.send();
-        if let Result::Ok(response) = send_result {
-            let json_result: Result<IssueResult> = response.json();
-            if let Result::Ok(issue) = json_result {
-                if issue.state.as_str() == "open" {
-                    return ValidationResult::StillValid;
+        if let Some(token) = context.github_token.as_ref().or(self.github_token.as_ref()) {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        let send_result: Result<Response> = request.send().await;
+        match send_result {
+            Result::Ok(response) => {
+                if is_rate_limited(&response) {
+                    eprintln!("GitHub rate limit hit while validating {}/{}#{}", owner, repo, number);
+                    return IssueState::Resolved(ValidationResult::Unknown);
                 }
-            } else {
-                eprintln!("Unable to unwrap json: {}", json_result.unwrap_err());
+                let json_result: Result<IssueResult> = response.json().await;
+                match json_result {
+                    Result::Ok(issue) => IssueState::Github(issue),
+                    Result::Err(error) => {
+                        eprintln!("Unable to unwrap json: {}", error);
+                        IssueState::Resolved(ValidationResult::Unknown)
+                    }
+                }
+            },
+            Result::Err(error) => {
+                eprintln!("bad response: {}", error);
+                IssueState::Resolved(ValidationResult::Unknown)
+            }
+        }
+    }
+
+    async fn gitlab_validation_result(&self, host: &str, project_path: &str, number: &str, issue_type: &GitlabIssueType, context: &ValidationContext) -> ValidationResult {
+        let issue_kind = match issue_type {
+            GitlabIssueType::Issue => "issues",
+            GitlabIssueType::MergeRequest => "merge_requests"
+        };
+
+        let encoded_project_path = project_path.replace('/', "%2F");
+        let request_url = format!(
+            "https://{host}/api/v4/projects/{path}/{issue_kind}/{number}",
+            host = host,
+            path = encoded_project_path,
+            issue_kind = issue_kind,
+            number = number
+        );
+        let mut request = self.client.get(&request_url)
+            .header("User-Agent", "younata/mdbook-section-validator");
+        if let Some(token) = context.gitlab_token.as_ref().or(self.gitlab_token.as_ref()) {
+            request = request.header("PRIVATE-TOKEN", token);
+        }
+        let send_result: Result<Response> = request.send().await;
+        match send_result {
+            Result::Ok(response) => {
+                if is_rate_limited(&response) {
+                    eprintln!("GitLab rate limit hit while validating {}/{}!{}", host, project_path, number);
+                    return ValidationResult::Unknown;
+                }
+                let json_result: Result<IssueResult> = response.json().await;
+                if let Result::Ok(issue) = json_result {
+                    if issue.state.as_str() == "opened" {
+                        return ValidationResult::StillValid;
+                    }
+                } else {
+                    eprintln!("Unable to unwrap json: {}", json_result.unwrap_err());
+                    return ValidationResult::Unknown;
+                }
+            },
+            Result::Err(error) => {
+                eprintln!("bad response: {}", error);
+                return ValidationResult::Unknown;
             }
-        } else {
-            eprintln!("bad response: {}", send_result.unwrap_err());
         }
         return ValidationResult::NoLongerValid;
     }
 
-    fn arbitrary_url_validation_result(&self, url: &Url) -> ValidationResult {
-        let client = Client::new();
-        let request = client.head(url.as_str())
+    async fn arbitrary_url_validation_result(&self, url: &Url) -> ValidationResult {
+        let request = self.client.head(url.as_str())
             .header("User-Agent", "younata/mdbook-section-validator");
-        let result: Result<Response> = request.send();
+        let result: Result<Response> = request.send().await;
 
         if let Result::Ok(response) = result {
             if response.status() == StatusCode::OK {
@@ -119,26 +325,137 @@ impl DefaultIssueValidator {
 #[cfg(test)]
 mod tests {
     use reqwest::Url;
-    use crate::issue_validator::{GithubIssueType, Issue, issue_from_url};
+    use crate::issue_validator::{GithubIssueType, GitlabIssueType, Issue, issue_from_url, ValidationCondition};
+    use super::{IssueResult, Label, github_condition_satisfied, is_rate_limited};
+
+    fn response(status: u16, headers: Vec<(&str, &str)>) -> reqwest::Response {
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in headers {
+            builder = builder.header(name, value);
+        }
+        builder.body(Vec::<u8>::new()).unwrap().into()
+    }
+
+    #[test]
+    fn is_rate_limited_true_on_forbidden_with_remaining_zero() {
+        assert!(is_rate_limited(&response(403, vec![("x-ratelimit-remaining", "0")])));
+    }
+
+    #[test]
+    fn is_rate_limited_true_on_too_many_requests_with_retry_after() {
+        assert!(is_rate_limited(&response(429, vec![("retry-after", "60")])));
+    }
+
+    #[test]
+    fn is_rate_limited_false_on_forbidden_without_rate_limit_headers() {
+        assert!(!is_rate_limited(&response(403, vec![])));
+    }
+
+    #[test]
+    fn is_rate_limited_false_on_too_many_requests_without_rate_limit_headers() {
+        assert!(!is_rate_limited(&response(429, vec![])));
+    }
+
+    #[test]
+    fn is_rate_limited_false_on_ok() {
+        assert!(!is_rate_limited(&response(200, vec![])));
+    }
+
+    fn issue_result(state: &str, merged_at: Option<&str>, labels: Vec<&str>) -> IssueResult {
+        IssueResult {
+            state: state.to_string(),
+            merged_at: merged_at.map(|value| value.to_string()),
+            labels: labels.into_iter().map(|name| Label { name: name.to_string() }).collect(),
+            milestone: None,
+        }
+    }
+
+    #[test]
+    fn github_condition_satisfied_while_open_checks_state() {
+        assert!(github_condition_satisfied(&issue_result("open", None, vec![]), &ValidationCondition::WhileOpen));
+        assert!(!github_condition_satisfied(&issue_result("closed", None, vec![]), &ValidationCondition::WhileOpen));
+    }
+
+    #[test]
+    fn github_condition_satisfied_until_merged_checks_merged_at() {
+        assert!(github_condition_satisfied(&issue_result("closed", None, vec![]), &ValidationCondition::UntilMerged));
+        assert!(!github_condition_satisfied(&issue_result("closed", Some("2024-01-01T00:00:00Z"), vec![]), &ValidationCondition::UntilMerged));
+    }
+
+    #[test]
+    fn github_condition_satisfied_while_labeled_checks_labels() {
+        let condition = ValidationCondition::WhileLabeled("wontfix".to_string());
+
+        assert!(github_condition_satisfied(&issue_result("open", None, vec!["wontfix"]), &condition));
+        assert!(!github_condition_satisfied(&issue_result("open", None, vec!["bug"]), &condition));
+    }
+
+    #[test]
+    fn validation_condition_parse_while_open_is_default() {
+        assert_eq!(ValidationCondition::parse("while-open"), ValidationCondition::WhileOpen);
+        assert_eq!(ValidationCondition::parse("something-unrecognized"), ValidationCondition::WhileOpen);
+        assert_eq!(ValidationCondition::default(), ValidationCondition::WhileOpen);
+    }
+
+    #[test]
+    fn validation_condition_parse_until_merged() {
+        assert_eq!(ValidationCondition::parse("until-merged"), ValidationCondition::UntilMerged);
+    }
+
+    #[test]
+    fn validation_condition_parse_while_labeled() {
+        assert_eq!(ValidationCondition::parse("while-labeled:wontfix"), ValidationCondition::WhileLabeled("wontfix".to_string()));
+    }
 
     #[test]
     fn issue_from_url_github_pr() {
         let url = Url::parse("https://github.com/rust-lang/mdBook/pull/1539").unwrap();
 
-        assert_eq!(issue_from_url(&url), Issue::Github("rust-lang", "mdBook", "1539", GithubIssueType::PullRequest, &url));
+        assert_eq!(issue_from_url(&url, &[]), Issue::Github("rust-lang", "mdBook", "1539", GithubIssueType::PullRequest, &url));
     }
 
     #[test]
     fn issue_from_url_github_issue() {
         let url = Url::parse("https://github.com/rust-lang/mdBook/issues/1538").unwrap();
 
-        assert_eq!(issue_from_url(&url), Issue::Github("rust-lang", "mdBook", "1538", GithubIssueType::Issue, &url));
+        assert_eq!(issue_from_url(&url, &[]), Issue::Github("rust-lang", "mdBook", "1538", GithubIssueType::Issue, &url));
     }
 
     #[test]
     fn issue_from_url_arbitrary_link() {
         let url = Url::parse("https://example.com").unwrap();
 
-        assert_eq!(issue_from_url(&url), Issue::Link(&url));
+        assert_eq!(issue_from_url(&url, &[]), Issue::Link(&url));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn issue_from_url_gitlab_issue() {
+        let url = Url::parse("https://gitlab.com/gitlab-org/gitlab/-/issues/1538").unwrap();
+
+        assert_eq!(issue_from_url(&url, &[]), Issue::Gitlab("gitlab.com", "gitlab-org/gitlab", "1538", GitlabIssueType::Issue, &url));
+    }
+
+    #[test]
+    fn issue_from_url_gitlab_merge_request() {
+        let url = Url::parse("https://gitlab.com/gitlab-org/gitlab/-/merge_requests/1539").unwrap();
+
+        assert_eq!(issue_from_url(&url, &[]), Issue::Gitlab("gitlab.com", "gitlab-org/gitlab", "1539", GitlabIssueType::MergeRequest, &url));
+    }
+
+    #[test]
+    fn issue_from_url_gitlab_self_hosted_subgroup() {
+        let url = Url::parse("https://gitlab.redox-os.org/redox-os/kernel/drivers/issues/42").unwrap();
+
+        assert_eq!(
+            issue_from_url(&url, &["gitlab.redox-os.org".to_string()]),
+            Issue::Gitlab("gitlab.redox-os.org", "redox-os/kernel/drivers", "42", GitlabIssueType::Issue, &url)
+        );
+    }
+
+    #[test]
+    fn issue_from_url_unlisted_self_hosted_gitlab_is_arbitrary_link() {
+        let url = Url::parse("https://gitlab.redox-os.org/redox-os/kernel/issues/42").unwrap();
+
+        assert_eq!(issue_from_url(&url, &[]), Issue::Link(&url));
+    }
+}
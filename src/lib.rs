@@ -1,6 +1,9 @@
 mod link_formatter;
+mod report;
 pub mod issue_validator;
 
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use regex::{Regex, Captures};
 
 use mdbook::book::{Book, BookItem};
@@ -8,19 +11,29 @@ use mdbook::errors::Error;
 use mdbook::preprocess::{Preprocessor, PreprocessorContext};
 use url::Url;
 use crate::link_formatter::LinkFormatter;
-use crate::issue_validator::{IssueValidator, issue_from_url, ValidationResult};
+use crate::issue_validator::{IssueValidator, IssueState, issue_from_url, ValidationResult, ValidationContext, ValidationCondition};
+use crate::report::{Report, ReportEntry};
 use futures::executor::block_on;
 use futures::stream::{self, StreamExt};
+use tokio::sync::Semaphore;
+
+const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 8;
 
 pub struct ValidatorProcessorOptions {
     hide_invalid: bool,
-    invalid_message: String
+    invalid_message: String,
+    max_concurrent_requests: usize,
+    github_token: Option<String>,
+    gitlab_token: Option<String>,
+    gitlab_hosts: Vec<String>,
+    report: Option<String>,
+    fail_on_invalid: bool,
 }
 
 #[derive(Debug, Eq, PartialEq)]
 enum ValidationSection {
     NonValidationSection(String),
-    ValidationSection(Vec<Url>, String),
+    ValidationSection(Vec<Url>, ValidationCondition, String),
 }
 
 pub struct ValidatorProcessor {
@@ -32,13 +45,35 @@ impl Preprocessor for ValidatorProcessor {
 
     fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book, Error> {
         let options = self.build_options(ctx);
+        let context = ValidationContext {
+            github_token: options.github_token.clone(),
+            gitlab_token: options.gitlab_token.clone(),
+        };
 
+        let links = ValidatorProcessor::all_links(&book);
+        let cache = self.validate_links(links, options.max_concurrent_requests, &context, &options.gitlab_hosts);
+
+        let mut report = Report::new();
         book.for_each_mut(|item| {
             if let BookItem::Chapter(chapter) = item {
-                chapter.content =
-                    self.process_chapter(&chapter.content, &options)
+                let chapter_label = chapter.path.as_ref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| chapter.name.clone());
+                let (content, entries) = self.process_chapter(&chapter_label, &chapter.content, &options, &cache);
+                chapter.content = content;
+                report.extend(entries);
             }
         });
+
+        if let Some(destination) = options.report.as_ref() {
+            report.write_to(destination)
+                .map_err(|error| Error::msg(format!("section-validator: failed to write report to {}: {}", destination, error)))?;
+        }
+
+        if options.fail_on_invalid && report.has_invalid() {
+            return Err(Error::msg("section-validator: one or more validated sections are no longer valid"));
+        }
+
         Ok(book)
     }
 
@@ -49,7 +84,13 @@ impl ValidatorProcessor {
     fn build_options(&self, ctx: &PreprocessorContext) -> ValidatorProcessorOptions {
         let mut options = ValidatorProcessorOptions {
             hide_invalid: true,
-            invalid_message: "🚨 Warning, this content is out of date and is included for historical reasons. 🚨".to_string()
+            invalid_message: "🚨 Warning, this content is out of date and is included for historical reasons. 🚨".to_string(),
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            github_token: None,
+            gitlab_token: None,
+            gitlab_hosts: Vec::new(),
+            report: None,
+            fail_on_invalid: false,
         };
 
         if let Some(config) = ctx.config.get_preprocessor("section-validator") {
@@ -59,43 +100,113 @@ impl ValidatorProcessor {
             if let Some(toml::value::Value::String(message)) = config.get("invalid_message") {
                 options.invalid_message = message.to_string();
             }
+            if let Some(toml::value::Value::Integer(max_concurrent_requests)) = config.get("max_concurrent_requests") {
+                if *max_concurrent_requests > 0 {
+                    options.max_concurrent_requests = *max_concurrent_requests as usize;
+                } else {
+                    eprintln!("section-validator: max_concurrent_requests must be positive, ignoring configured value of {}", max_concurrent_requests);
+                }
+            }
+            if let Some(toml::value::Value::String(github_token)) = config.get("github_token") {
+                options.github_token = Some(github_token.to_string());
+            }
+            if let Some(toml::value::Value::String(gitlab_token)) = config.get("gitlab_token") {
+                options.gitlab_token = Some(gitlab_token.to_string());
+            }
+            if let Some(toml::value::Value::Array(gitlab_hosts)) = config.get("gitlab_hosts") {
+                options.gitlab_hosts = gitlab_hosts.iter()
+                    .filter_map(|value| value.as_str().map(|host| host.to_string()))
+                    .collect();
+            }
+            if let Some(toml::value::Value::String(report)) = config.get("report") {
+                options.report = Some(report.to_string());
+            }
+            if let Some(toml::value::Value::Boolean(fail_on_invalid)) = config.get("fail_on_invalid") {
+                options.fail_on_invalid = *fail_on_invalid;
+            }
         }
 
         options
     }
 
+    fn all_links(book: &Book) -> HashSet<Url> {
+        let mut links = HashSet::new();
+        for item in book.iter() {
+            if let BookItem::Chapter(chapter) = item {
+                for section in ValidatorProcessor::validation_sections(&chapter.content) {
+                    if let ValidationSection::ValidationSection(section_links, _condition, _) = section {
+                        links.extend(section_links.into_iter());
+                    }
+                }
+            }
+        }
+        links
+    }
+
+    fn validate_links(&self, links: HashSet<Url>, max_concurrent_requests: usize, context: &ValidationContext, gitlab_hosts: &[String]) -> HashMap<Url, IssueState> {
+        block_on(self.validate_links_async(links, max_concurrent_requests, context, gitlab_hosts))
+    }
+
+    async fn validate_links_async(&self, links: HashSet<Url>, max_concurrent_requests: usize, context: &ValidationContext, gitlab_hosts: &[String]) -> HashMap<Url, IssueState> {
+        let semaphore = Arc::new(Semaphore::new(max_concurrent_requests));
+
+        stream::iter(links.into_iter())
+            .map(|url| {
+                let semaphore = Arc::clone(&semaphore);
+                let context = context.clone();
+                async move {
+                    let _permit = semaphore.acquire().await.unwrap();
+                    let issue = issue_from_url(&url, gitlab_hosts);
+                    let state = self.validator.validate(&issue, &context).await;
+                    (url, state)
+                }
+            })
+            .buffer_unordered(max_concurrent_requests)
+            .collect::<HashMap<Url, IssueState>>()
+            .await
+    }
+
     fn process_chapter(
         &self,
+        chapter_name: &str,
         raw_content: &str,
-        options: &ValidatorProcessorOptions
-    ) -> String {
+        options: &ValidatorProcessorOptions,
+        cache: &HashMap<Url, IssueState>,
+    ) -> (String, Vec<ReportEntry>) {
         let mut content = String::new();
+        let mut report_entries = Vec::new();
         for section in ValidatorProcessor::validation_sections(raw_content) {
             match section {
                 ValidationSection::NonValidationSection(text) => {
                     content.push_str(&text);
                 },
-                ValidationSection::ValidationSection(links, text) => {
-                    let validation_result = self.is_section_valid(links.clone());
+                ValidationSection::ValidationSection(links, condition, text) => {
+                    let validation_result = self.is_section_valid(&links, &condition, cache);
+                    report_entries.push(ReportEntry {
+                        chapter: chapter_name.to_string(),
+                        links: links.clone(),
+                        condition: condition.clone(),
+                        result: validation_result,
+                    });
                     if options.hide_invalid && validation_result == ValidationResult::NoLongerValid {
                         continue;
                     }
                     content.push_str(&*format!("<div class=\"validated-content\" links=\"{}\">\n\n", ValidatorProcessor::links_joined(&links)));
-                    if validation_result == ValidationResult::NoLongerValid {
-                        content.push_str(&*options.invalid_message);
-                    } else {
-                        let mut is_or_are = "is";
-                        if links.len() != 1 {
-                            is_or_are = "are";
-                        }
-                        content.push_str(&*format!("⚠️ This is only valid while {} {} open", LinkFormatter::markdown_many(&links), is_or_are));
+                    let mut is_or_are = "is";
+                    if links.len() != 1 {
+                        is_or_are = "are";
+                    }
+                    match validation_result {
+                        ValidationResult::NoLongerValid => content.push_str(&*options.invalid_message),
+                        ValidationResult::Unknown => content.push_str(&*format!("⚠️ Couldn't confirm whether {} {} still open, so this content is being shown as a precaution", LinkFormatter::markdown_many(&links, &options.gitlab_hosts), is_or_are)),
+                        ValidationResult::StillValid => content.push_str(&*format!("⚠️ This is only valid while {} {} open", LinkFormatter::markdown_many(&links, &options.gitlab_hosts), is_or_are)),
                     }
                     content.push_str(&text);
                     content.push_str("\n</div>");
                 }
             }
         }
-        content
+        (content, report_entries)
     }
 
     fn validation_sections(raw_content: &str) -> Vec<ValidationSection> {
@@ -119,8 +230,10 @@ impl ValidatorProcessor {
 
             last_endpoint = mat.end();
 
+            let (links, condition) = ValidatorProcessor::parse_header(capture.get(1).unwrap().as_str());
             sections.push(ValidationSection::ValidationSection(
-                ValidatorProcessor::links_to_check(capture.get(1).unwrap().as_str()),
+                links,
+                condition,
                 capture.get(2).unwrap().as_str().to_string()
             ))
         }
@@ -133,6 +246,13 @@ impl ValidatorProcessor {
         return sections;
     }
 
+    fn parse_header(header: &str) -> (Vec<Url>, ValidationCondition) {
+        match header.split_once(" condition=") {
+            Some((links, condition)) => (ValidatorProcessor::links_to_check(links), ValidationCondition::parse(condition.trim())),
+            None => (ValidatorProcessor::links_to_check(header), ValidationCondition::default()),
+        }
+    }
+
     fn links_to_check(links: &str) -> Vec<Url> {
         links.split(",").map(|text| Url::parse(text).unwrap()).collect()
     }
@@ -142,17 +262,16 @@ impl ValidatorProcessor {
         links_strs.join(",")
     }
 
-    fn is_section_valid(&self, links: Vec<Url>) -> ValidationResult {
-        let stream = stream::unfold(links.into_iter(), |mut links| async {
-            let url = links.next()?;
-            let issue = issue_from_url(&url);
-            let response = self.validator.validate(&issue).await;
-            Some((response, links))
-        });
-        let result = block_on(async { stream.collect::<Vec<ValidationResult>>().await });
-        result.into_iter().reduce(|a, b| {
-            if a == ValidationResult::StillValid && b == ValidationResult::StillValid { a } else { ValidationResult::NoLongerValid }
-        }).unwrap()
+    fn is_section_valid(&self, links: &Vec<Url>, condition: &ValidationCondition, cache: &HashMap<Url, IssueState>) -> ValidationResult {
+        links.iter()
+            .map(|url| cache.get(url).map(|state| state.resolve(condition)).unwrap_or(ValidationResult::Unknown))
+            .reduce(|a, b| {
+                match (a, b) {
+                    (ValidationResult::StillValid, ValidationResult::StillValid) => ValidationResult::StillValid,
+                    (ValidationResult::NoLongerValid, _) | (_, ValidationResult::NoLongerValid) => ValidationResult::NoLongerValid,
+                    _ => ValidationResult::Unknown,
+                }
+            }).unwrap()
     }
 }
 
@@ -162,7 +281,7 @@ mod tests {
     use super::ValidatorProcessor;
     use super::ValidationSection;
     use url::Url;
-    use crate::issue_validator::{Issue, ValidationResult};
+    use crate::issue_validator::{Issue, IssueState, ValidationResult, ValidationContext, ValidationCondition};
     use crate::ValidatorProcessorOptions;
     use async_trait::async_trait;
 
@@ -185,6 +304,7 @@ other content";
             sections.get(1).unwrap(),
             &ValidationSection::ValidationSection(
                 vec![Url::parse("https://github.com/example/example/issues/1").unwrap()],
+                ValidationCondition::WhileOpen,
                 "\n\nsome content to be conditionally included.\n\n".to_string()
             )
         );
@@ -214,6 +334,7 @@ other content to be conditionally included.
             sections.get(0).unwrap(),
             &ValidationSection::ValidationSection(
                 vec![Url::parse("https://github.com/example/example/issues/1").unwrap()],
+                ValidationCondition::WhileOpen,
                 "\n\nsome content to be conditionally included.\n\n".to_string()
             )
         );
@@ -225,6 +346,7 @@ other content to be conditionally included.
                     Url::parse("https://github.com/example/example/issues/1").unwrap(),
                     Url::parse("https://github.com/example/example/issues/2").unwrap()
                 ],
+                ValidationCondition::WhileOpen,
                 "\n\nother content to be conditionally included.\n\n".to_string()
             )
         );
@@ -246,11 +368,16 @@ other content
 
         let processor = ValidatorProcessor { validator: Box::new(validator) };
 
-        let options = ValidatorProcessorOptions { hide_invalid: true, invalid_message: "".to_string() };
+        let options = ValidatorProcessorOptions { hide_invalid: true, invalid_message: "".to_string(), max_concurrent_requests: 8, github_token: None, gitlab_token: None, gitlab_hosts: Vec::new(), report: None, fail_on_invalid: false };
+
+        let links = links_in_content(content);
+        let cache = processor.validate_links(links, options.max_concurrent_requests, &ValidationContext::default(), &options.gitlab_hosts);
 
-        let received_chapter = processor.process_chapter(
+        let (received_chapter, _report_entries) = processor.process_chapter(
+            "chapter-1",
             content,
-            &options
+            &options,
+            &cache
         );
 
         let expected_chapter = "whatever
@@ -284,12 +411,25 @@ other content
 
         let processor = ValidatorProcessor { validator: Box::new(validator) };
 
-        let received_chapter = processor.process_chapter(
+        let options = ValidatorProcessorOptions {
+            hide_invalid: true,
+            invalid_message: "🚨 Warning, this content is out of date and is included for historical reasons. 🚨".to_string(),
+            max_concurrent_requests: 8,
+            github_token: None,
+            gitlab_token: None,
+            gitlab_hosts: Vec::new(),
+            report: None,
+            fail_on_invalid: false,
+        };
+
+        let links = links_in_content(content);
+        let cache = processor.validate_links(links, options.max_concurrent_requests, &ValidationContext::default(), &options.gitlab_hosts);
+
+        let (received_chapter, _report_entries) = processor.process_chapter(
+            "chapter-1",
             content,
-            &ValidatorProcessorOptions {
-                hide_invalid: true,
-                invalid_message: "🚨 Warning, this content is out of date and is included for historical reasons. 🚨".to_string()
-            }
+            &options,
+            &cache
         );
 
         let expected_chapter = "whatever
@@ -316,12 +456,25 @@ other content
 
         let processor = ValidatorProcessor { validator: Box::new(validator) };
 
-        let received_chapter = processor.process_chapter(
+        let options = ValidatorProcessorOptions {
+            hide_invalid: false,
+            invalid_message: "🚨 Warning, this content is out of date and is included for historical reasons. 🚨".to_string(),
+            max_concurrent_requests: 8,
+            github_token: None,
+            gitlab_token: None,
+            gitlab_hosts: Vec::new(),
+            report: None,
+            fail_on_invalid: false,
+        };
+
+        let links = links_in_content(content);
+        let cache = processor.validate_links(links, options.max_concurrent_requests, &ValidationContext::default(), &options.gitlab_hosts);
+
+        let (received_chapter, _report_entries) = processor.process_chapter(
+            "chapter-1",
             content,
-            &ValidatorProcessorOptions {
-                hide_invalid: false,
-                invalid_message: "🚨 Warning, this content is out of date and is included for historical reasons. 🚨".to_string()
-            }
+            &options,
+            &cache
         );
 
         let expected_chapter = "whatever
@@ -339,9 +492,107 @@ other content
         assert_eq!(received_chapter, expected_chapter.to_string());
     }
 
+    #[test]
+    fn test_content_unknown_still_included_with_softer_notice() {
+        let content = "whatever
+!!!https://github.com/example/example/issues/1
+
+some content to be conditionally included.
+
+!!!
+
+other content
+        ";
+
+        let validator = FakeIssueValidator { validate_behavior: ValidateBehavior::AllUnknown };
+
+        let processor = ValidatorProcessor { validator: Box::new(validator) };
+
+        let options = ValidatorProcessorOptions {
+            hide_invalid: true,
+            invalid_message: "🚨 Warning, this content is out of date and is included for historical reasons. 🚨".to_string(),
+            max_concurrent_requests: 8,
+            github_token: None,
+            gitlab_token: None,
+            gitlab_hosts: Vec::new(),
+            report: None,
+            fail_on_invalid: false,
+        };
+
+        let links = links_in_content(content);
+        let cache = processor.validate_links(links, options.max_concurrent_requests, &ValidationContext::default(), &options.gitlab_hosts);
+
+        let (received_chapter, _report_entries) = processor.process_chapter(
+            "chapter-1",
+            content,
+            &options,
+            &cache
+        );
+
+        let expected_chapter = "whatever
+<div class=\"validated-content\" links=\"https://github.com/example/example/issues/1\">
+
+⚠️ Couldn't confirm whether [`example/example#1`](https://github.com/example/example/issues/1) is still open, so this content is being shown as a precaution
+
+some content to be conditionally included.
+
+
+</div>
+
+other content
+        ";
+        assert_eq!(received_chapter, expected_chapter.to_string());
+    }
+
+    #[test]
+    fn test_process_chapter_reports_one_entry_per_section() {
+        let content = "whatever
+!!!https://github.com/example/example/issues/1
+
+some content to be conditionally included.
+
+!!!
+
+other content
+        ";
+
+        let validator = FakeIssueValidator { validate_behavior: ValidateBehavior::NoneValid };
+
+        let processor = ValidatorProcessor { validator: Box::new(validator) };
+
+        let options = ValidatorProcessorOptions {
+            hide_invalid: true,
+            invalid_message: "".to_string(),
+            max_concurrent_requests: 8,
+            github_token: None,
+            gitlab_token: None,
+            gitlab_hosts: Vec::new(),
+            report: None,
+            fail_on_invalid: false,
+        };
+
+        let links = links_in_content(content);
+        let cache = processor.validate_links(links, options.max_concurrent_requests, &ValidationContext::default(), &options.gitlab_hosts);
+
+        let (_received_chapter, report_entries) = processor.process_chapter(
+            "intro.md",
+            content,
+            &options,
+            &cache
+        );
+
+        assert_eq!(report_entries.len(), 1);
+        let entry = report_entries.get(0).unwrap();
+        assert_eq!(entry.chapter, "intro.md".to_string());
+        assert_eq!(entry.links, vec![Url::parse("https://github.com/example/example/issues/1").unwrap()]);
+        assert_eq!(entry.condition, ValidationCondition::WhileOpen);
+        assert_eq!(entry.result, ValidationResult::NoLongerValid);
+    }
+
     enum ValidateBehavior {
         AllValid,
-        NoneValid
+        NoneValid,
+        AllUnknown,
     }
 
     struct FakeIssueValidator {
@@ -350,13 +601,24 @@ other content
 
     #[async_trait]
     impl IssueValidator for FakeIssueValidator {
-        async fn validate(&self, _link: &Issue) -> ValidationResult {
+        async fn validate(&self, _link: &Issue, _context: &ValidationContext) -> IssueState {
             async {
                 match &self.validate_behavior {
-                    ValidateBehavior::NoneValid => ValidationResult::NoLongerValid,
-                    ValidateBehavior::AllValid => ValidationResult::StillValid
+                    ValidateBehavior::NoneValid => IssueState::Resolved(ValidationResult::NoLongerValid),
+                    ValidateBehavior::AllValid => IssueState::Resolved(ValidationResult::StillValid),
+                    ValidateBehavior::AllUnknown => IssueState::Resolved(ValidationResult::Unknown),
                 }
             }.await
         }
     }
-}
\ No newline at end of file
+
+    fn links_in_content(content: &str) -> std::collections::HashSet<Url> {
+        let mut links = std::collections::HashSet::new();
+        for section in ValidatorProcessor::validation_sections(content) {
+            if let ValidationSection::ValidationSection(section_links, _condition, _) = section {
+                links.extend(section_links.into_iter());
+            }
+        }
+        links
+    }
+}
@@ -4,8 +4,8 @@ use crate::issue_validator::{Issue, issue_from_url};
 pub struct LinkFormatter;
 
 impl LinkFormatter {
-    pub fn markdown_many(links: &Vec<Url>) -> String {
-        let markdown_links: Vec<String> = links.into_iter().map(|l| LinkFormatter::markdown_single(&l)).collect();
+    pub fn markdown_many(links: &Vec<Url>, gitlab_hosts: &[String]) -> String {
+        let markdown_links: Vec<String> = links.into_iter().map(|l| LinkFormatter::markdown_single(&l, gitlab_hosts)).collect();
         if markdown_links.len() == 1 {
             markdown_links.last().unwrap().to_string()
         } else if markdown_links.len() == 2 {
@@ -19,9 +19,10 @@ impl LinkFormatter {
         }
     }
 
-    fn markdown_single(link: &Url) -> String {
-        match issue_from_url(link) {
+    fn markdown_single(link: &Url, gitlab_hosts: &[String]) -> String {
+        match issue_from_url(link, gitlab_hosts) {
             Issue::Github(owner, repo, number, _, url) => format!("[`{}/{}#{}`]({})", owner, repo, number, url.as_str()),
+            Issue::Gitlab(_, project_path, number, _, url) => format!("[`{}#{}`]({})", project_path, number, url.as_str()),
             Issue::Link(url) => format!("[`{}`]({})", url.as_str(), url.as_str()),
         }
     }
@@ -35,15 +36,23 @@ mod tests {
     #[test]
     fn markdown_single_github_link() {
         assert_eq!(
-            LinkFormatter::markdown_single(&Url::parse("https://github.com/foo/bar/issues/1").unwrap()),
+            LinkFormatter::markdown_single(&Url::parse("https://github.com/foo/bar/issues/1").unwrap(), &[]),
             "[`foo/bar#1`](https://github.com/foo/bar/issues/1)".to_string()
         )
     }
 
+    #[test]
+    fn markdown_single_gitlab_link() {
+        assert_eq!(
+            LinkFormatter::markdown_single(&Url::parse("https://gitlab.com/foo/bar/-/issues/1").unwrap(), &[]),
+            "[`foo/bar#1`](https://gitlab.com/foo/bar/-/issues/1)".to_string()
+        )
+    }
+
     #[test]
     fn markdown_single_non_github_link() {
         assert_eq!(
-            LinkFormatter::markdown_single(&Url::parse("https://www.example.com/foo/bar/issues/1").unwrap()),
+            LinkFormatter::markdown_single(&Url::parse("https://www.example.com/foo/bar/issues/1").unwrap(), &[]),
             "[`https://www.example.com/foo/bar/issues/1`](https://www.example.com/foo/bar/issues/1)".to_string()
         )
     }
@@ -53,7 +62,7 @@ mod tests {
         assert_eq!(
             LinkFormatter::markdown_many(&vec![
                 Url::parse("https://github.com/foo/bar/issues/1").unwrap()
-            ]),
+            ], &[]),
             "[`foo/bar#1`](https://github.com/foo/bar/issues/1)".to_string()
         )
     }
@@ -64,7 +73,7 @@ mod tests {
             LinkFormatter::markdown_many(&vec![
                 Url::parse("https://github.com/foo/bar/issues/1").unwrap(),
                 Url::parse("https://www.example.com/foo/bar/issues/1").unwrap()
-            ]),
+            ], &[]),
             "[`foo/bar#1`](https://github.com/foo/bar/issues/1), and [`https://www.example.com/foo/bar/issues/1`](https://www.example.com/foo/bar/issues/1)".to_string()
         )
     }
@@ -76,7 +85,7 @@ mod tests {
                 Url::parse("https://github.com/foo/bar/issues/1").unwrap(),
                 Url::parse("https://www.example.com/foo/bar/issues/1").unwrap(),
                 Url::parse("https://github.com/bar/foo/issues/3").unwrap()
-            ]),
+            ], &[]),
             "[`foo/bar#1`](https://github.com/foo/bar/issues/1), [`https://www.example.com/foo/bar/issues/1`](https://www.example.com/foo/bar/issues/1), and [`bar/foo#3`](https://github.com/bar/foo/issues/3)".to_string()
         )
     }
@@ -21,13 +21,14 @@ pub fn make_app() -> App<'static, 'static> {
 async fn main() {
     let matches = make_app().get_matches();
     let preprocessor = ValidatorProcessor {
-        validator: Box::new(DefaultIssueValidator)
+        validator: Box::new(DefaultIssueValidator::new())
     };
     if let Some(sub_args) = matches.subcommand_matches("supports") {
         handle_supports(&preprocessor, sub_args);
     }
     if let Err(e) = handle_preprocessing(&preprocessor) {
         eprintln!("{}", e);
+        process::exit(1);
     }
 }
 
@@ -0,0 +1,165 @@
+use url::Url;
+use crate::issue_validator::{ValidationCondition, ValidationResult};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportEntry {
+    pub chapter: String,
+    pub links: Vec<Url>,
+    pub condition: ValidationCondition,
+    pub result: ValidationResult,
+}
+
+pub struct Report {
+    entries: Vec<ReportEntry>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Report { entries: Vec::new() }
+    }
+
+    pub fn extend(&mut self, entries: Vec<ReportEntry>) {
+        self.entries.extend(entries);
+    }
+
+    pub fn has_invalid(&self) -> bool {
+        self.entries.iter().any(|entry| entry.result == ValidationResult::NoLongerValid)
+    }
+
+    pub fn write_to(&self, destination: &str) -> std::io::Result<()> {
+        let rendered = if destination.ends_with(".json") {
+            self.render_json()
+        } else {
+            self.render_markdown()
+        };
+
+        if destination == "stdout" {
+            println!("{}", rendered);
+            Ok(())
+        } else {
+            std::fs::write(destination, rendered)
+        }
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut markdown = String::from("| Chapter | Links | Condition | Result |\n|---|---|---|---|\n");
+        for entry in &self.entries {
+            let links = entry.links.iter().map(|link| link.as_str()).collect::<Vec<&str>>().join(", ");
+            markdown.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                entry.chapter,
+                links,
+                condition_label(&entry.condition),
+                result_label(entry.result),
+            ));
+        }
+        markdown
+    }
+
+    fn render_json(&self) -> String {
+        let entries: Vec<String> = self.entries.iter().map(|entry| {
+            let links = entry.links.iter()
+                .map(|link| format!("{:?}", link.as_str()))
+                .collect::<Vec<String>>()
+                .join(",");
+            format!(
+                "{{\"chapter\":{:?},\"links\":[{}],\"condition\":{:?},\"result\":{:?}}}",
+                entry.chapter,
+                links,
+                condition_label(&entry.condition),
+                result_label(entry.result),
+            )
+        }).collect();
+        format!("[{}]", entries.join(","))
+    }
+}
+
+fn result_label(result: ValidationResult) -> &'static str {
+    match result {
+        ValidationResult::StillValid => "still_valid",
+        ValidationResult::NoLongerValid => "no_longer_valid",
+        ValidationResult::Unknown => "unknown",
+    }
+}
+
+fn condition_label(condition: &ValidationCondition) -> String {
+    match condition {
+        ValidationCondition::WhileOpen => "while-open".to_string(),
+        ValidationCondition::UntilMerged => "until-merged".to_string(),
+        ValidationCondition::WhileLabeled(label) => format!("while-labeled:{}", label),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Report, ReportEntry};
+    use crate::issue_validator::{ValidationCondition, ValidationResult};
+    use url::Url;
+
+    #[test]
+    fn has_invalid_is_false_when_nothing_no_longer_valid() {
+        let mut report = Report::new();
+        report.extend(vec![
+            ReportEntry {
+                chapter: "intro.md".to_string(),
+                links: vec![Url::parse("https://github.com/example/example/issues/1").unwrap()],
+                condition: ValidationCondition::WhileOpen,
+                result: ValidationResult::StillValid,
+            },
+            ReportEntry {
+                chapter: "intro.md".to_string(),
+                links: vec![Url::parse("https://github.com/example/example/issues/2").unwrap()],
+                condition: ValidationCondition::WhileOpen,
+                result: ValidationResult::Unknown,
+            },
+        ]);
+
+        assert_eq!(report.has_invalid(), false);
+    }
+
+    #[test]
+    fn has_invalid_is_true_when_a_section_is_no_longer_valid() {
+        let mut report = Report::new();
+        report.extend(vec![ReportEntry {
+            chapter: "intro.md".to_string(),
+            links: vec![Url::parse("https://github.com/example/example/issues/1").unwrap()],
+            condition: ValidationCondition::UntilMerged,
+            result: ValidationResult::NoLongerValid,
+        }]);
+
+        assert_eq!(report.has_invalid(), true);
+    }
+
+    #[test]
+    fn render_markdown_includes_a_row_per_entry() {
+        let mut report = Report::new();
+        report.extend(vec![ReportEntry {
+            chapter: "intro.md".to_string(),
+            links: vec![Url::parse("https://github.com/example/example/issues/1").unwrap()],
+            condition: ValidationCondition::UntilMerged,
+            result: ValidationResult::NoLongerValid,
+        }]);
+
+        let markdown = report.render_markdown();
+
+        assert!(markdown.contains("| intro.md | https://github.com/example/example/issues/1 | until-merged | no_longer_valid |"));
+    }
+
+    #[test]
+    fn render_json_produces_one_object_per_entry() {
+        let mut report = Report::new();
+        report.extend(vec![ReportEntry {
+            chapter: "intro.md".to_string(),
+            links: vec![Url::parse("https://github.com/example/example/issues/1").unwrap()],
+            condition: ValidationCondition::WhileLabeled("wontfix".to_string()),
+            result: ValidationResult::StillValid,
+        }]);
+
+        let json = report.render_json();
+
+        assert_eq!(
+            json,
+            "[{\"chapter\":\"intro.md\",\"links\":[\"https://github.com/example/example/issues/1\"],\"condition\":\"while-labeled:wontfix\",\"result\":\"still_valid\"}]".to_string()
+        );
+    }
+}